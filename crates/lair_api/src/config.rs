@@ -0,0 +1,305 @@
+//! Runtime configuration shared by both the client and server halves of
+//! an ipc connection.
+
+use crate::internal::ipc::Transport;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Runtime configuration for a lair ipc client or server.
+///
+/// Build one with [`Config::builder`]; every accessor below has a
+/// reasonable default for local, same-host development, so the only
+/// thing most callers need to set is `root_path`.
+pub struct Config {
+    root_path: PathBuf,
+    transport: Transport,
+    channel_capacity: usize,
+    idle_timeout: Duration,
+    min_msg_interval: Duration,
+    max_in_flight_requests: usize,
+    max_connections: usize,
+    default_request_timeout: Duration,
+    interactive_request_timeout: Duration,
+    keepalive_interval: Duration,
+    connection_drain_deadline: Duration,
+    request_reap_interval: Duration,
+    tcp_bind_addr: SocketAddr,
+    tcp_connect_addr: SocketAddr,
+    tls_server_name: String,
+    tls_server_config: Option<std::sync::Arc<tokio_rustls::rustls::ServerConfig>>,
+    tls_client_config: Option<std::sync::Arc<tokio_rustls::rustls::ClientConfig>>,
+}
+
+impl Config {
+    /// Start building a new `Config`.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Directory this keystore's local socket / persisted state lives
+    /// under.
+    pub fn root_path(&self) -> &Path {
+        &self.root_path
+    }
+
+    /// Which transport new connections are established over.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Channel capacity used for every internal mpsc queue backing a
+    /// connection (low-level wire frames, `IpcWireApi` requests, incoming
+    /// connections).
+    pub fn channel_capacity(&self) -> usize {
+        self.channel_capacity
+    }
+
+    /// How long a read half may go without receiving a single byte
+    /// (including a bare `Pong`) before the connection is presumed dead.
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// The steady-state spacing a well-behaved peer is expected to honor
+    /// between messages; the flood-control token bucket refills at this
+    /// rate.
+    pub fn min_msg_interval(&self) -> Duration {
+        self.min_msg_interval
+    }
+
+    /// Maximum inbound requests this side will process concurrently on a
+    /// single connection before newly arriving requests are dropped.
+    pub fn max_in_flight_requests(&self) -> usize {
+        self.max_in_flight_requests
+    }
+
+    /// Maximum number of connections a single bound server will keep
+    /// alive at once.
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    /// Default per-request timeout for machine-to-machine requests made
+    /// via `IpcWireApi::request`.
+    pub fn default_request_timeout(&self) -> Duration {
+        self.default_request_timeout
+    }
+
+    /// Timeout applied to interactive requests (e.g.
+    /// `ToCliRequestUnlockPassphrase`) that wait on a human, not a peer
+    /// process - set well above `default_request_timeout` so a person
+    /// typing a passphrase doesn't get raced by it.
+    pub fn interactive_request_timeout(&self) -> Duration {
+        self.interactive_request_timeout
+    }
+
+    /// How often a connection emits a keepalive `Ping`.
+    pub fn keepalive_interval(&self) -> Duration {
+        self.keepalive_interval
+    }
+
+    /// How long a connection waits, once Draining, for in-flight requests
+    /// to finish before it is hard-killed.
+    pub fn connection_drain_deadline(&self) -> Duration {
+        self.connection_drain_deadline
+    }
+
+    /// How often `RespondTrack` is scanned for expired requests.
+    pub fn request_reap_interval(&self) -> Duration {
+        self.request_reap_interval
+    }
+
+    /// Address a tcp+tls server binds to.
+    pub fn tcp_bind_addr(&self) -> SocketAddr {
+        self.tcp_bind_addr
+    }
+
+    /// Address a tcp+tls client connects to.
+    pub fn tcp_connect_addr(&self) -> SocketAddr {
+        self.tcp_connect_addr
+    }
+
+    /// Expected server name a tcp+tls client verifies the peer
+    /// certificate against.
+    pub fn tls_server_name(&self) -> &str {
+        &self.tls_server_name
+    }
+
+    /// Tls config used by a tcp+tls server. Must require client
+    /// certificate auth (e.g. `AllowAnyAuthenticatedClient`) - see
+    /// `ConfigBuilder::set_tls_server_config`.
+    pub fn tls_server_config(
+        &self,
+    ) -> std::sync::Arc<tokio_rustls::rustls::ServerConfig> {
+        self.tls_server_config.clone().expect(
+            "Transport::Tcp requires ConfigBuilder::set_tls_server_config",
+        )
+    }
+
+    /// Tls config used by a tcp+tls client, carrying this side's own
+    /// client certificate - see `ConfigBuilder::set_tls_client_config`.
+    pub fn tls_client_config(
+        &self,
+    ) -> std::sync::Arc<tokio_rustls::rustls::ClientConfig> {
+        self.tls_client_config.clone().expect(
+            "Transport::Tcp requires ConfigBuilder::set_tls_client_config",
+        )
+    }
+}
+
+/// Builder for [`Config`].
+pub struct ConfigBuilder {
+    root_path: PathBuf,
+    transport: Transport,
+    channel_capacity: usize,
+    idle_timeout: Duration,
+    min_msg_interval: Duration,
+    max_in_flight_requests: usize,
+    max_connections: usize,
+    default_request_timeout: Duration,
+    interactive_request_timeout: Duration,
+    keepalive_interval: Duration,
+    connection_drain_deadline: Duration,
+    request_reap_interval: Duration,
+    tcp_bind_addr: SocketAddr,
+    tcp_connect_addr: SocketAddr,
+    tls_server_name: String,
+    tls_server_config: Option<std::sync::Arc<tokio_rustls::rustls::ServerConfig>>,
+    tls_client_config: Option<std::sync::Arc<tokio_rustls::rustls::ClientConfig>>,
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self {
+            root_path: PathBuf::from("."),
+            transport: Transport::Local,
+            channel_capacity: 32,
+            idle_timeout: Duration::from_secs(60),
+            min_msg_interval: Duration::from_millis(10),
+            max_in_flight_requests: 32,
+            max_connections: 64,
+            default_request_timeout: Duration::from_secs(30),
+            interactive_request_timeout: Duration::from_secs(5 * 60),
+            keepalive_interval: Duration::from_secs(10),
+            connection_drain_deadline: Duration::from_secs(10),
+            request_reap_interval: Duration::from_secs(1),
+            tcp_bind_addr: ([127, 0, 0, 1], 0).into(),
+            tcp_connect_addr: ([127, 0, 0, 1], 0).into(),
+            tls_server_name: "localhost".to_string(),
+            tls_server_config: None,
+            tls_client_config: None,
+        }
+    }
+}
+
+impl ConfigBuilder {
+    /// Directory this keystore's local socket / persisted state lives
+    /// under.
+    pub fn set_root_path(mut self, root_path: impl AsRef<Path>) -> Self {
+        self.root_path = root_path.as_ref().to_owned();
+        self
+    }
+
+    /// Select which transport new connections are established over.
+    pub fn set_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Override the default per-request timeout - see
+    /// `Config::default_request_timeout`.
+    pub fn set_default_request_timeout(
+        mut self,
+        timeout: Duration,
+    ) -> Self {
+        self.default_request_timeout = timeout;
+        self
+    }
+
+    /// Override the interactive-request timeout - see
+    /// `Config::interactive_request_timeout`.
+    pub fn set_interactive_request_timeout(
+        mut self,
+        timeout: Duration,
+    ) -> Self {
+        self.interactive_request_timeout = timeout;
+        self
+    }
+
+    /// Override the steady-state flood-control spacing - see
+    /// `Config::min_msg_interval`.
+    pub fn set_min_msg_interval(mut self, interval: Duration) -> Self {
+        self.min_msg_interval = interval;
+        self
+    }
+
+    /// Override the request reap interval - see
+    /// `Config::request_reap_interval`.
+    pub fn set_request_reap_interval(mut self, interval: Duration) -> Self {
+        self.request_reap_interval = interval;
+        self
+    }
+
+    /// Override the address a tcp+tls server binds to / a client
+    /// connects to.
+    pub fn set_tcp_addr(mut self, addr: SocketAddr) -> Self {
+        self.tcp_bind_addr = addr;
+        self.tcp_connect_addr = addr;
+        self
+    }
+
+    /// Set the server name a tcp+tls client verifies the peer
+    /// certificate against.
+    pub fn set_tls_server_name(mut self, name: impl Into<String>) -> Self {
+        self.tls_server_name = name.into();
+        self
+    }
+
+    /// Provide the tls server config used by `Transport::Tcp` servers.
+    /// Must be built with a client-cert `Verifier` (e.g.
+    /// `AllowAnyAuthenticatedClient`), not `NoClientAuth` - this
+    /// transport crosses a trust boundary, so the tls handshake is this
+    /// crate's only authentication step.
+    pub fn set_tls_server_config(
+        mut self,
+        config: std::sync::Arc<tokio_rustls::rustls::ServerConfig>,
+    ) -> Self {
+        self.tls_server_config = Some(config);
+        self
+    }
+
+    /// Provide the tls client config used by `Transport::Tcp` clients,
+    /// carrying this side's own client certificate + key to satisfy the
+    /// server's client-auth requirement.
+    pub fn set_tls_client_config(
+        mut self,
+        config: std::sync::Arc<tokio_rustls::rustls::ClientConfig>,
+    ) -> Self {
+        self.tls_client_config = Some(config);
+        self
+    }
+
+    /// Finalize this builder into an `Arc<Config>`.
+    pub fn build(self) -> std::sync::Arc<Config> {
+        std::sync::Arc::new(Config {
+            root_path: self.root_path,
+            transport: self.transport,
+            channel_capacity: self.channel_capacity,
+            idle_timeout: self.idle_timeout,
+            min_msg_interval: self.min_msg_interval,
+            max_in_flight_requests: self.max_in_flight_requests,
+            max_connections: self.max_connections,
+            default_request_timeout: self.default_request_timeout,
+            interactive_request_timeout: self.interactive_request_timeout,
+            keepalive_interval: self.keepalive_interval,
+            connection_drain_deadline: self.connection_drain_deadline,
+            request_reap_interval: self.request_reap_interval,
+            tcp_bind_addr: self.tcp_bind_addr,
+            tcp_connect_addr: self.tcp_connect_addr,
+            tls_server_name: self.tls_server_name,
+            tls_server_config: self.tls_server_config,
+            tls_client_config: self.tls_client_config,
+        })
+    }
+}
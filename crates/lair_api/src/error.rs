@@ -0,0 +1,37 @@
+//! Lair's single error type, shared by every public and internal api.
+
+/// The error type returned by every fallible lair_api function.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum LairError {
+    /// A request did not receive a response before its deadline elapsed.
+    #[error("request timed out")]
+    Timeout,
+
+    /// A peer was disconnected for sending messages faster than the
+    /// connection's flood-control budget allows.
+    #[error("flood control")]
+    FloodControl,
+
+    /// Catch-all wrapping an error from another crate (io, tls, codec...).
+    #[error(transparent)]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl LairError {
+    /// Build an `Other` variant from any std error.
+    pub fn other(
+        e: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        LairError::Other(e.into())
+    }
+}
+
+impl From<ghost_actor::GhostError> for LairError {
+    fn from(e: ghost_actor::GhostError) -> Self {
+        LairError::other(e)
+    }
+}
+
+/// `Result` alias using `LairError`.
+pub type LairResult<T> = ::std::result::Result<T, LairError>;
@@ -1,4 +1,4 @@
-//! Abstraction over unix domain sockets / windows named pipes
+//! Abstraction over unix domain sockets / windows named pipes / tcp+tls
 
 use crate::internal::wire::*;
 use crate::*;
@@ -6,17 +6,144 @@ use crate::*;
 use futures::{future::FutureExt, sink::SinkExt, stream::StreamExt};
 use ghost_actor::dependencies::tracing;
 use std::collections::HashMap;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 #[cfg(not(windows))]
 mod unix_ipc;
 #[cfg(not(windows))]
-use unix_ipc::*;
+use unix_ipc as local_ipc;
 
 #[cfg(windows)]
 mod win_ipc;
 #[cfg(windows)]
-use win_ipc::*;
+use win_ipc as local_ipc;
+
+mod tcp_ipc;
+
+/// Which transport a connection is established over. Local sockets stay
+/// confined to same-host callers; tcp+tls lets a remote conductor reach
+/// this lair keystore across a trust boundary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// Unix domain socket / windows named pipe.
+    Local,
+    /// TCP wrapped in a rustls session.
+    Tcp,
+}
+
+/// Read half of either a local or a tcp+tls connection.
+pub enum IpcRead {
+    /// Local unix socket / named pipe read half.
+    Local(local_ipc::IpcRead),
+    /// Tcp+tls read half.
+    Tcp(tcp_ipc::IpcRead),
+}
+
+/// Write half of either a local or a tcp+tls connection.
+pub enum IpcWrite {
+    /// Local unix socket / named pipe write half.
+    Local(local_ipc::IpcWrite),
+    /// Tcp+tls write half.
+    Tcp(tcp_ipc::IpcWrite),
+}
+
+impl AsyncRead for IpcRead {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            IpcRead::Local(r) => std::pin::Pin::new(r).poll_read(cx, buf),
+            IpcRead::Tcp(r) => std::pin::Pin::new(r).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for IpcWrite {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            IpcWrite::Local(w) => std::pin::Pin::new(w).poll_write(cx, buf),
+            IpcWrite::Tcp(w) => std::pin::Pin::new(w).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            IpcWrite::Local(w) => std::pin::Pin::new(w).poll_flush(cx),
+            IpcWrite::Tcp(w) => std::pin::Pin::new(w).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            IpcWrite::Local(w) => std::pin::Pin::new(w).poll_shutdown(cx),
+            IpcWrite::Tcp(w) => std::pin::Pin::new(w).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Bound listener, dispatching `accept()` to whichever transport the
+/// `Config` selects.
+pub enum IpcServer {
+    /// Local unix socket / named pipe listener.
+    Local(local_ipc::IpcServer),
+    /// Tcp+tls listener.
+    Tcp(tcp_ipc::IpcServer),
+}
+
+impl IpcServer {
+    /// Bind a new listener for the transport configured in `config`.
+    pub fn bind(config: Arc<Config>) -> LairResult<Self> {
+        Ok(match config.transport() {
+            Transport::Local => {
+                IpcServer::Local(local_ipc::IpcServer::bind(config)?)
+            }
+            Transport::Tcp => IpcServer::Tcp(tcp_ipc::IpcServer::bind(config)?),
+        })
+    }
+
+    /// Accept the next incoming connection, performing the transport's
+    /// handshake (if any) before handing back the read/write halves.
+    pub async fn accept(&mut self) -> LairResult<(IpcRead, IpcWrite)> {
+        match self {
+            IpcServer::Local(srv) => {
+                let (read_half, write_half) = srv.accept().await?;
+                Ok((IpcRead::Local(read_half), IpcWrite::Local(write_half)))
+            }
+            IpcServer::Tcp(srv) => {
+                let (read_half, write_half) = srv.accept().await?;
+                Ok((IpcRead::Tcp(read_half), IpcWrite::Tcp(write_half)))
+            }
+        }
+    }
+}
+
+/// Establish an outgoing connection for the transport configured in
+/// `config`, performing the handshake (if any) before returning the
+/// read/write halves.
+async fn ipc_connect(config: Arc<Config>) -> LairResult<(IpcRead, IpcWrite)> {
+    match config.transport() {
+        Transport::Local => {
+            let (read_half, write_half) = local_ipc::ipc_connect(config).await?;
+            Ok((IpcRead::Local(read_half), IpcWrite::Local(write_half)))
+        }
+        Transport::Tcp => {
+            let (read_half, write_half) = tcp_ipc::ipc_connect(config).await?;
+            Ok((IpcRead::Tcp(read_half), IpcWrite::Tcp(write_half)))
+        }
+    }
+}
 
 ghost_actor::ghost_chan! {
     /// Low-level send api..
@@ -64,8 +191,9 @@ where
 fn spawn_low_level_write_half(
     kill_switch: KillSwitch,
     mut write_half: IpcWrite,
+    chan_capacity: usize,
 ) -> LairResult<LowLevelWireSender> {
-    let (s, mut r) = futures::channel::mpsc::channel(10);
+    let (s, mut r) = futures::channel::mpsc::channel(chan_capacity);
 
     err_spawn("ll-write", async move {
         while let Some(msg) = r.next().await {
@@ -98,22 +226,93 @@ fn spawn_low_level_write_half(
 fn spawn_low_level_read_half(
     kill_switch: KillSwitch,
     mut read_half: IpcRead,
+    idle_deadline: std::time::Duration,
+    min_msg_interval: std::time::Duration,
+    chan_capacity: usize,
 ) -> LairResult<LowLevelWireReceiver> {
-    let (s, r) = futures::channel::mpsc::channel(10);
+    let (s, r) = futures::channel::mpsc::channel(chan_capacity);
+
+    // Token bucket: lets a peer burst up to `FLOOD_BURST` messages before
+    // `min_msg_interval` spacing kicks in, rather than killing the
+    // connection the instant two messages land in the same instant (e.g.
+    // a legitimate client pipelining a handful of requests at startup).
+    // A peer that's merely draining its burst sees its next message
+    // delayed, nothing more. `flood_strikes` is what actually bounds a
+    // *persistently* flooding peer: each delayed message - one that found
+    // the bucket already short of a full token - counts as a strike, and
+    // a message spaced out enough to find the bucket topped back up to a
+    // full token resets it to zero. Tracking debt in `flood_tokens` alone
+    // can't do this, since the `delay_for` below always refills the token
+    // it just spent, so the bucket steady-states instead of running away.
+    const FLOOD_BURST: f64 = 8.0;
+    const FLOOD_STRIKES_LIMIT: u32 = 8;
+    let mut flood_tokens: f64 = FLOOD_BURST;
+    let mut flood_strikes: u32 = 0;
 
     err_spawn("ll-read", async move {
         let mut pending_data = Vec::new();
         let mut buffer = [0_u8; 4096];
+        let mut last_refill_at = tokio::time::Instant::now();
         loop {
-            let read = read_half
-                .read(&mut buffer)
-                .await
-                .map_err(LairError::other)?;
+            let read = match tokio::time::timeout(
+                idle_deadline,
+                read_half.read(&mut buffer),
+            )
+            .await
+            {
+                Ok(read) => read.map_err(LairError::other)?,
+                Err(_) => {
+                    // No bytes at all (not even a Pong) within the idle
+                    // deadline - the peer is presumed dead, skip draining
+                    // and tear the connection down now.
+                    tracing::warn!("ll-read idle timeout, killing connection");
+                    kill_switch.hard_kill();
+                    break;
+                }
+            };
+            if read == 0 {
+                // A clean peer close reads as an immediate `Ok(0)`, not a
+                // pending read the idle timeout above would ever catch -
+                // without this check the loop would spin re-reading `Ok(0)`
+                // forever instead of tearing the connection down.
+                tracing::debug!("ll-read EOF, killing connection");
+                kill_switch.hard_kill();
+                break;
+            }
             pending_data.extend_from_slice(&buffer[..read]);
             while let Ok(size) = LairWire::peek_size(&pending_data) {
                 if pending_data.len() < size {
                     break;
                 }
+
+                // Flood control: refill the token bucket for the time that
+                // has passed, then spend one token per message. Running
+                // dry just slows the peer down (a brief delay before its
+                // next message is accepted); only a peer that racks up more
+                // than `FLOOD_STRIKES_LIMIT` delayed messages in a row - never
+                // spacing out enough to earn a strike-resetting full token
+                // - gets its connection dropped outright.
+                let now = tokio::time::Instant::now();
+                let elapsed = now.saturating_duration_since(last_refill_at);
+                last_refill_at = now;
+                flood_tokens = (flood_tokens
+                    + elapsed.as_secs_f64() / min_msg_interval.as_secs_f64())
+                .min(FLOOD_BURST);
+                if flood_tokens < 1.0 {
+                    flood_strikes += 1;
+                    if flood_strikes > FLOOD_STRIKES_LIMIT {
+                        tracing::warn!(
+                            "ll-read flood control tripped, killing connection"
+                        );
+                        kill_switch.hard_kill();
+                        return Err(LairError::FloodControl);
+                    }
+                    tokio::time::delay_for(min_msg_interval).await;
+                } else {
+                    flood_strikes = 0;
+                }
+                flood_tokens -= 1.0;
+
                 let msg = LairWire::decode(&pending_data)?;
                 tracing::trace!("ll read {:?}", msg);
                 let _ = pending_data.drain(..size);
@@ -133,8 +332,8 @@ fn spawn_low_level_read_half(
 pub async fn spawn_ipc_connection(
     config: Arc<Config>,
 ) -> LairResult<(KillSwitch, IpcSender, IpcReceiver)> {
-    let (read_half, write_half) = ipc_connect(config).await?;
-    spawn_connection_pair(read_half, write_half)
+    let (read_half, write_half) = ipc_connect(config.clone()).await?;
+    spawn_connection_pair(config, read_half, write_half)
 }
 
 /// Spawn/bind a new ipc listener connection awaiting incomming clients.
@@ -142,27 +341,56 @@ pub async fn spawn_bind_ipc(
     config: Arc<Config>,
 ) -> LairResult<(KillSwitch, IncomingIpcReceiver)> {
     let kill_switch = KillSwitch::new();
-    let (in_send, in_recv) = futures::channel::mpsc::channel(10);
+    let (in_send, in_recv) =
+        futures::channel::mpsc::channel(config.channel_capacity());
 
-    let srv = IpcServer::bind(config)?;
+    let srv = IpcServer::bind(config.clone())?;
 
     err_spawn(
         "srv-bind",
-        srv_main_bind_task(kill_switch.clone(), srv, in_send),
+        srv_main_bind_task(config, kill_switch.clone(), srv, in_send),
     );
 
     Ok((kill_switch, in_recv))
 }
 
 async fn srv_main_bind_task(
+    config: Arc<Config>,
     kill_switch: KillSwitch,
     mut srv: IpcServer,
     mut in_send: IncomingIpcSender,
 ) -> LairResult<()> {
+    // Caps how many connections this server keeps alive at once, so a
+    // burst of incoming connections can't outrun the work we're willing
+    // to do for them. A permit is handed back once its connection's
+    // `KillSwitch` reaches Dead, not when `accept()` returns it.
+    let max_connections =
+        Arc::new(tokio::sync::Semaphore::new(config.max_connections()));
+
     loop {
         if let Ok((read_half, write_half)) = srv.accept().await {
-            let (con_kill_switch, send, recv) =
-                spawn_connection_pair(read_half, write_half)?;
+            match max_connections.try_acquire() {
+                Ok(permit) => permit.forget(),
+                Err(_) => {
+                    tracing::warn!(
+                        "max connections reached, rejecting connection"
+                    );
+                    continue;
+                }
+            }
+
+            let (con_kill_switch, send, recv) = spawn_connection_pair(
+                config.clone(),
+                read_half,
+                write_half,
+            )?;
+
+            let release_sem = max_connections.clone();
+            let release_kill = con_kill_switch.clone();
+            tokio::task::spawn(async move {
+                release_kill.wait_dead().await;
+                release_sem.add_permits(1);
+            });
 
             in_send
                 .send((con_kill_switch, send, recv))
@@ -177,19 +405,33 @@ async fn srv_main_bind_task(
 }
 
 fn spawn_connection_pair(
+    config: Arc<Config>,
     read_half: IpcRead,
     write_half: IpcWrite,
 ) -> LairResult<(KillSwitch, IpcSender, IpcReceiver)> {
     let respond_track = RespondTrack::new();
     let kill_switch = KillSwitch::new();
 
-    let writer = spawn_low_level_write_half(kill_switch.clone(), write_half)?;
-    let reader = spawn_low_level_read_half(kill_switch.clone(), read_half)?;
+    let writer = spawn_low_level_write_half(
+        kill_switch.clone(),
+        write_half,
+        config.channel_capacity(),
+    )?;
+    let reader = spawn_low_level_read_half(
+        kill_switch.clone(),
+        read_half,
+        config.idle_timeout(),
+        config.min_msg_interval(),
+        config.channel_capacity(),
+    )?;
 
     let (outgoing_msg_send, outgoing_msg_recv) =
-        futures::channel::mpsc::channel(10);
+        futures::channel::mpsc::channel(config.channel_capacity());
     let (incoming_msg_send, incoming_msg_recv) =
-        futures::channel::mpsc::channel(10);
+        futures::channel::mpsc::channel(config.channel_capacity());
+
+    let max_in_flight_requests =
+        Arc::new(tokio::sync::Semaphore::new(config.max_in_flight_requests()));
 
     err_spawn(
         "con-write",
@@ -198,16 +440,43 @@ fn spawn_connection_pair(
             kill_switch.clone(),
             outgoing_msg_recv,
             writer.clone(),
+            config.default_request_timeout(),
+            config.interactive_request_timeout(),
         ),
     );
     err_spawn(
         "con-read",
         spawn_read_task(
-            respond_track,
+            respond_track.clone(),
             kill_switch.clone(),
             incoming_msg_send,
             reader,
+            writer.clone(),
+            max_in_flight_requests,
+        ),
+    );
+    err_spawn(
+        "con-keepalive",
+        spawn_keepalive_task(
+            kill_switch.clone(),
             writer,
+            config.keepalive_interval(),
+        ),
+    );
+    err_spawn(
+        "con-drain",
+        spawn_drain_task(
+            respond_track.clone(),
+            kill_switch.clone(),
+            config.connection_drain_deadline(),
+        ),
+    );
+    err_spawn(
+        "con-reap",
+        spawn_reap_task(
+            respond_track,
+            kill_switch.clone(),
+            config.request_reap_interval(),
         ),
     );
 
@@ -219,11 +488,47 @@ async fn spawn_write_task(
     kill_switch: KillSwitch,
     mut outgoing_msg_recv: IpcReceiver,
     writer: LowLevelWireSender,
+    default_timeout: std::time::Duration,
+    interactive_timeout: std::time::Duration,
 ) -> LairResult<()> {
-    while let Some(msg) = outgoing_msg_recv.next().await {
+    // While Draining we stop pulling *new* requests off the channel, but
+    // leave already-registered `RespondTrack` entries alone so the read
+    // task can still deliver their responses.
+    while !kill_switch.is_draining() {
+        // Also wake on Draining, so a request that arrives after shutdown
+        // has started isn't pulled just because we were parked on `next()`.
+        let msg = tokio::select! {
+            _ = kill_switch.wait_draining() => break,
+            msg = outgoing_msg_recv.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+        };
         match msg {
             IpcWireApi::Request { respond, msg, .. } => {
-                respond_track.register(msg.get_msg_id(), respond).await;
+                // Interactive requests (e.g. waiting on a human typing a
+                // passphrase) get their own, much more generous timeout
+                // instead of racing `default_request_timeout`, which is
+                // sized for machine-to-machine round trips.
+                let timeout = if msg.is_interactive() {
+                    interactive_timeout
+                } else {
+                    default_timeout
+                };
+                respond_track
+                    .register(msg.get_msg_id(), respond, timeout)
+                    .await;
+                writer.low_level_send(msg).await?;
+            }
+            IpcWireApi::RequestTimeout {
+                respond,
+                msg,
+                timeout,
+                ..
+            } => {
+                respond_track
+                    .register(msg.get_msg_id(), respond, timeout)
+                    .await;
                 writer.low_level_send(msg).await?;
             }
         }
@@ -234,12 +539,108 @@ async fn spawn_write_task(
     Ok(())
 }
 
+/// While the connection is Draining, wait for all in-flight `RespondTrack`
+/// entries to be delivered (or a configured deadline to elapse) before
+/// hard-killing the connection and tearing down the read/write halves.
+async fn spawn_drain_task(
+    respond_track: RespondTrack,
+    kill_switch: KillSwitch,
+    drain_deadline: std::time::Duration,
+) -> LairResult<()> {
+    // No polling while the connection is healthy - the common case is a
+    // long-lived connection that never drains at all.
+    kill_switch.wait_draining().await;
+    if kill_switch.is_dead() {
+        return Ok(());
+    }
+    // Only the (bounded, short-lived) Draining window itself polls, while
+    // waiting for in-flight `RespondTrack` entries to clear out.
+    let deadline = tokio::time::Instant::now() + drain_deadline;
+    while !respond_track.is_empty().await
+        && tokio::time::Instant::now() < deadline
+    {
+        tokio::time::delay_for(std::time::Duration::from_millis(25)).await;
+    }
+    kill_switch.hard_kill();
+    Ok(())
+}
+
+/// Periodically scan `RespondTrack` for entries whose per-request timeout
+/// has elapsed, removing them and failing their caller with
+/// `LairError::Timeout` rather than leaving them to leak forever.
+async fn spawn_reap_task(
+    respond_track: RespondTrack,
+    kill_switch: KillSwitch,
+    reap_interval: std::time::Duration,
+) -> LairResult<()> {
+    let mut interval = tokio::time::interval(reap_interval);
+    loop {
+        interval.tick().await;
+        respond_track.reap_expired().await;
+        if kill_switch.is_dead() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Periodically emit a `LairWire::Ping` so a peer whose socket is still
+/// open but whose application has stopped responding gets caught by the
+/// idle timeout in `spawn_low_level_read_half` instead of parking forever.
+async fn spawn_keepalive_task(
+    kill_switch: KillSwitch,
+    writer: LowLevelWireSender,
+    keepalive_interval: std::time::Duration,
+) -> LairResult<()> {
+    let mut interval = tokio::time::interval(keepalive_interval);
+    loop {
+        interval.tick().await;
+        if !kill_switch.cont() {
+            break;
+        }
+        if writer
+            .low_level_send(LairWire::Ping { msg_id: 0 })
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// An acquired slot against a per-connection in-flight-request limit.
+/// `tokio::sync::Semaphore::acquire` hands back a permit borrowed from the
+/// semaphore, which can't be stored in a `'static` spawned task - so we
+/// `forget()` it and return the permit manually on drop instead.
+struct InFlightPermit(Arc<tokio::sync::Semaphore>);
+
+impl Drop for InFlightPermit {
+    fn drop(&mut self) {
+        self.0.add_permits(1);
+    }
+}
+
+/// Try to take a slot against a per-connection in-flight-request limit
+/// without blocking. Returns `None` once the limit is saturated, so a
+/// caller sharing this connection's single demux loop with every other
+/// inbound message (`Pong`s, responses destined for `RespondTrack`) can
+/// reject/backpressure the request instead of blocking that loop - and
+/// everyone else on it - until a slot frees up.
+fn try_acquire_in_flight_permit(
+    max_in_flight_requests: &Arc<tokio::sync::Semaphore>,
+) -> Option<InFlightPermit> {
+    max_in_flight_requests.try_acquire().ok()?.forget();
+    Some(InFlightPermit(max_in_flight_requests.clone()))
+}
+
 async fn spawn_read_task(
     respond_track: RespondTrack,
     kill_switch: KillSwitch,
     incoming_msg_send: IpcSender,
     mut reader: LowLevelWireReceiver,
     writer: LowLevelWireSender,
+    max_in_flight_requests: Arc<tokio::sync::Semaphore>,
 ) -> LairResult<()> {
     while let Some(msg) = reader.next().await {
         match msg {
@@ -248,17 +649,58 @@ async fn spawn_read_task(
                 // next message.
                 respond.respond(Ok(async move { Ok(()) }.boxed().into()));
 
-                if msg.is_req() {
-                    let fut = incoming_msg_send.request(msg);
-                    let writer_clone = writer.clone();
-                    err_spawn("req-mini", async move {
-                        if let Ok(res) = fut.await {
-                            let _ = writer_clone.low_level_send(res).await;
+                match msg {
+                    // Ping/Pong are handled transparently here and never
+                    // surfaced to `IpcWireApi` consumers.
+                    LairWire::Ping { msg_id } => {
+                        let _ = writer
+                            .low_level_send(LairWire::Pong { msg_id })
+                            .await;
+                    }
+                    LairWire::Pong { .. } => {
+                        // Any bytes - including a bare Pong - already fed
+                        // `spawn_low_level_read_half`'s idle timeout, so
+                        // there's nothing further to do with it.
+                    }
+                    msg if msg.is_req() => {
+                        // Never blocks this connection's shared demux loop:
+                        // other messages (Pongs, responses queued against
+                        // `respond_track`) have to keep flowing even while
+                        // the in-flight limit is saturated.
+                        match try_acquire_in_flight_permit(
+                            &max_in_flight_requests,
+                        ) {
+                            Some(permit) => {
+                                let fut = incoming_msg_send.request(msg);
+                                let writer_clone = writer.clone();
+                                err_spawn("req-mini", async move {
+                                    if let Ok(res) = fut.await {
+                                        let _ = writer_clone
+                                            .low_level_send(res)
+                                            .await;
+                                    }
+                                    drop(permit);
+                                    LairResult::<()>::Ok(())
+                                });
+                            }
+                            None => {
+                                // Saturated - drop the request rather than
+                                // block. The sender already registered its
+                                // own deadline in its `RespondTrack` when it
+                                // issued the request, so this eventually
+                                // surfaces to it as `LairError::Timeout`
+                                // instead of hanging forever.
+                                tracing::warn!(
+                                    "in-flight limit reached, \
+                                     dropping request {}",
+                                    msg.get_msg_id()
+                                );
+                            }
                         }
-                        LairResult::<()>::Ok(())
-                    });
-                } else {
-                    respond_track.respond(msg).await;
+                    }
+                    msg => {
+                        respond_track.respond(msg).await;
+                    }
                 }
             }
         }
@@ -269,25 +711,111 @@ async fn spawn_read_task(
     Ok(())
 }
 
-/// If any of these are dropped, they all say we should stop looping.
+/// Running -> Draining -> Dead. Running is the normal operating state.
+/// Draining means shutdown has started but in-flight requests tracked by
+/// `RespondTrack` should still be allowed to complete. Dead means the
+/// connection should tear down immediately.
+const KILL_SWITCH_RUNNING: u8 = 0;
+const KILL_SWITCH_DRAINING: u8 = 1;
+const KILL_SWITCH_DEAD: u8 = 2;
+
+struct KillSwitchInner {
+    // `state` is the single source of truth; `tx` only exists to wake up
+    // `wait_draining`/`wait_dead` callers without polling. We're on
+    // tokio 0.2 here (see `delay_for`/`#[tokio::test(threaded_scheduler)]`
+    // elsewhere in this file), which predates `tokio::sync::Notify`, so a
+    // `watch` channel (whose carried value we ignore) stands in for it.
+    state: std::sync::atomic::AtomicU8,
+    tx: tokio::sync::watch::Sender<()>,
+}
+
+/// If any of these are dropped, they all say we should start draining.
+/// Use `hard_kill()` to skip straight to a full, immediate teardown.
 #[derive(Clone)]
-pub struct KillSwitch(Arc<std::sync::atomic::AtomicBool>);
+pub struct KillSwitch {
+    inner: Arc<KillSwitchInner>,
+    rx: tokio::sync::watch::Receiver<()>,
+}
 
 impl Drop for KillSwitch {
     fn drop(&mut self) {
-        self.0.store(false, std::sync::atomic::Ordering::Relaxed)
+        // Only move the state machine forward - never revive a Dead switch.
+        if self
+            .inner
+            .state
+            .compare_exchange(
+                KILL_SWITCH_RUNNING,
+                KILL_SWITCH_DRAINING,
+                std::sync::atomic::Ordering::Relaxed,
+                std::sync::atomic::Ordering::Relaxed,
+            )
+            .is_ok()
+        {
+            let _ = self.inner.tx.broadcast(());
+        }
     }
 }
 
 impl KillSwitch {
     /// Create a new kill switch
     pub fn new() -> Self {
-        Self(Arc::new(std::sync::atomic::AtomicBool::new(true)))
+        let (tx, rx) = tokio::sync::watch::channel(());
+        Self {
+            inner: Arc::new(KillSwitchInner {
+                state: std::sync::atomic::AtomicU8::new(KILL_SWITCH_RUNNING),
+                tx,
+            }),
+            rx,
+        }
     }
 
-    /// Should we continue?
+    /// Should we continue? (i.e. not yet Dead)
     pub fn cont(&self) -> bool {
-        self.0.load(std::sync::atomic::Ordering::Relaxed)
+        self.inner.state.load(std::sync::atomic::Ordering::Relaxed)
+            != KILL_SWITCH_DEAD
+    }
+
+    /// Have we entered the Draining (or Dead) state?
+    pub fn is_draining(&self) -> bool {
+        self.inner.state.load(std::sync::atomic::Ordering::Relaxed)
+            != KILL_SWITCH_RUNNING
+    }
+
+    /// Have we reached the Dead state?
+    pub fn is_dead(&self) -> bool {
+        self.inner.state.load(std::sync::atomic::Ordering::Relaxed)
+            == KILL_SWITCH_DEAD
+    }
+
+    /// Skip straight to the Dead state, immediately tearing down the
+    /// connection rather than waiting for in-flight requests to drain.
+    pub fn hard_kill(&self) {
+        self.inner
+            .state
+            .store(KILL_SWITCH_DEAD, std::sync::atomic::Ordering::Relaxed);
+        let _ = self.inner.tx.broadcast(());
+    }
+
+    /// Block (without polling) until this kill switch enters the Draining
+    /// (or Dead) state. Resolves immediately if it already has.
+    pub async fn wait_draining(&self) {
+        let mut rx = self.rx.clone();
+        while !self.is_draining() {
+            if rx.recv().await.is_none() {
+                break;
+            }
+        }
+    }
+
+    /// Block (without polling) until this kill switch reaches the Dead
+    /// state. Resolves immediately if it already has.
+    pub async fn wait_dead(&self) {
+        let mut rx = self.rx.clone();
+        while !self.is_dead() {
+            if rx.recv().await.is_none() {
+                break;
+            }
+        }
     }
 }
 
@@ -297,33 +825,78 @@ impl Default for KillSwitch {
     }
 }
 
+/// A `RespondTrack` entry: the pending responder plus the instant at which
+/// it should be reaped if no response has arrived by then.
+type RespondTrackEntry = (IpcRespond, tokio::time::Instant);
+
 #[derive(Clone)]
-struct RespondTrack(Arc<tokio::sync::Mutex<HashMap<u64, IpcRespond>>>);
+struct RespondTrack(Arc<tokio::sync::Mutex<HashMap<u64, RespondTrackEntry>>>);
 
 impl RespondTrack {
     pub fn new() -> Self {
         Self(Arc::new(tokio::sync::Mutex::new(HashMap::new())))
     }
 
-    pub async fn register(&self, msg_id: u64, respond: IpcRespond) {
+    pub async fn register(
+        &self,
+        msg_id: u64,
+        respond: IpcRespond,
+        timeout: std::time::Duration,
+    ) {
         let mut lock = self.0.lock().await;
-        lock.insert(msg_id, respond);
+        lock.insert(msg_id, (respond, tokio::time::Instant::now() + timeout));
     }
 
     pub async fn respond(&self, msg: LairWire) {
         let mut lock = self.0.lock().await;
         let msg_id = msg.get_msg_id();
-        if let Some(respond) = lock.remove(&msg_id) {
+        if let Some((respond, _)) = lock.remove(&msg_id) {
             respond.respond(Ok(async move { Ok(msg) }.boxed().into()));
         }
     }
+
+    /// Are there any requests still awaiting a response?
+    pub async fn is_empty(&self) -> bool {
+        self.0.lock().await.is_empty()
+    }
+
+    /// Remove and fail with `LairError::Timeout` any entries whose
+    /// per-request deadline has already passed.
+    pub async fn reap_expired(&self) {
+        let now = tokio::time::Instant::now();
+        let mut lock = self.0.lock().await;
+        let expired: Vec<u64> = lock
+            .iter()
+            .filter(|(_, (_, expires_at))| *expires_at <= now)
+            .map(|(msg_id, _)| *msg_id)
+            .collect();
+        for msg_id in expired {
+            if let Some((respond, _)) = lock.remove(&msg_id) {
+                respond.respond(Ok(async move {
+                    Err(LairError::Timeout)
+                }
+                .boxed()
+                .into()));
+            }
+        }
+    }
 }
 
 ghost_actor::ghost_chan! {
     /// Ipc wire api for both incoming api requsets and outgoing event requests.
     pub chan IpcWireApi<LairError> {
-        /// Make an Ipc request.
+        /// Make an Ipc request, using the default request timeout from
+        /// `Config`. Interactive requests sent the other direction over
+        /// this channel (e.g. `ToCliRequestUnlockPassphrase`, which waits
+        /// on a human typing a passphrase) are exempt from this default -
+        /// `spawn_write_task` registers those against
+        /// `Config::interactive_request_timeout` instead, so a slow human
+        /// can't be raced by a timeout sized for machine-to-machine RPCs.
+        /// Use `request_timeout` for tighter, request-specific bounds.
         fn request(msg: LairWire) -> LairWire;
+
+        /// Make an Ipc request, overriding the default request timeout.
+        fn request_timeout(msg: LairWire, timeout: std::time::Duration) -> LairWire;
     }
 }
 
@@ -366,7 +939,10 @@ mod tests {
                     while let Some(msg) = con_recv.next().await {
                         println!("GOT MESSAGE!!: {:?}", msg);
                         match msg {
-                            IpcWireApi::Request { respond, msg, .. } => {
+                            IpcWireApi::Request { respond, msg, .. }
+                            | IpcWireApi::RequestTimeout {
+                                respond, msg, ..
+                            } => {
                                 println!("GOT MESSAGE!!: {:?}", msg);
                                 if let LairWire::ToLairLairGetLastEntryIndex {
                                     msg_id,
@@ -398,7 +974,8 @@ mod tests {
             spawn_ipc_connection(config).await?;
 
         match cli_recv.next().await.unwrap() {
-            IpcWireApi::Request { respond, msg, .. } => {
+            IpcWireApi::Request { respond, msg, .. }
+            | IpcWireApi::RequestTimeout { respond, msg, .. } => {
                 println!("GOT: {:?}", msg);
                 match msg {
                     LairWire::ToCliRequestUnlockPassphrase { msg_id } => {
@@ -438,4 +1015,244 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn test_ipc_request_timeout() -> LairResult<()> {
+        let tmpdir = tempfile::tempdir().unwrap();
+
+        let config = Config::builder().set_root_path(tmpdir.path()).build();
+
+        let (srv_kill, mut srv_recv) = spawn_bind_ipc(config.clone()).await?;
+
+        let srv_task_kill = srv_kill.clone();
+        err_spawn("test-timeout-srv", async move {
+            while let Some((con_kill, _con_send, mut con_recv)) =
+                srv_recv.next().await
+            {
+                err_spawn("test-timeout-con", async move {
+                    // deliberately never respond, so the client side's
+                    // request_timeout is the only thing that can resolve
+                    // the pending request.
+                    while con_recv.next().await.is_some() {
+                        if !con_kill.cont() {
+                            break;
+                        }
+                    }
+                    LairResult::<()>::Ok(())
+                });
+                if !srv_task_kill.cont() {
+                    break;
+                }
+            }
+            LairResult::<()>::Ok(())
+        });
+
+        let (cli_kill, cli_send, _cli_recv) =
+            spawn_ipc_connection(config).await?;
+
+        let res = cli_send
+            .request_timeout(
+                LairWire::ToLairLairGetLastEntryIndex { msg_id: 1 },
+                std::time::Duration::from_millis(20),
+            )
+            .await;
+
+        match res {
+            Err(LairError::Timeout) => (),
+            oth => panic!("expected LairError::Timeout, got: {:?}", oth),
+        }
+
+        drop(cli_kill);
+        drop(srv_kill);
+        drop(tmpdir);
+
+        Ok(())
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn test_drain_delivers_in_flight_response() -> LairResult<()> {
+        let tmpdir = tempfile::tempdir().unwrap();
+
+        let config = Config::builder().set_root_path(tmpdir.path()).build();
+
+        let (srv_kill, mut srv_recv) = spawn_bind_ipc(config.clone()).await?;
+
+        // Signals once the server has actually received the request, so
+        // the test doesn't drop the client's `KillSwitch` before
+        // `spawn_write_task` has dequeued and registered it - at which
+        // point it's no longer a "new" request Draining would skip, but
+        // an in-flight one Draining must still let complete.
+        let (got_req_send, got_req_recv) = futures::channel::oneshot::channel();
+        let mut got_req_send = Some(got_req_send);
+
+        let srv_task_kill = srv_kill.clone();
+        err_spawn("test-drain-srv", async move {
+            while let Some((con_kill, con_send, mut con_recv)) =
+                srv_recv.next().await
+            {
+                let mut got_req_send = got_req_send.take();
+                err_spawn("test-drain-con", async move {
+                    // Held for the lifetime of this task - dropping it
+                    // early would close the connection's outgoing channel,
+                    // ending `spawn_write_task` and tipping the whole
+                    // connection into Draining before the client's
+                    // request even arrives.
+                    let _con_send = con_send;
+                    while let Some(msg) = con_recv.next().await {
+                        match msg {
+                            IpcWireApi::Request { respond, msg, .. }
+                            | IpcWireApi::RequestTimeout {
+                                respond, msg, ..
+                            } => {
+                                if let Some(tx) = got_req_send.take() {
+                                    let _ = tx.send(());
+                                }
+                                if let LairWire::ToLairLairGetLastEntryIndex {
+                                    msg_id,
+                                } = msg
+                                {
+                                    // Answer only after the client has had
+                                    // a chance to drop its own KillSwitch,
+                                    // to prove Draining still delivers
+                                    // this response rather than cutting it
+                                    // off.
+                                    tokio::time::delay_for(
+                                        std::time::Duration::from_millis(50),
+                                    )
+                                    .await;
+                                    respond.respond(Ok(async move {
+                                        Ok(LairWire::ToCliLairGetLastEntryIndexResponse {
+                                            msg_id,
+                                            last_keystore_index: 7.into(),
+                                        })
+                                    }.boxed().into()));
+                                }
+                            }
+                        }
+                        if !con_kill.cont() {
+                            break;
+                        }
+                    }
+                    LairResult::<()>::Ok(())
+                });
+                if !srv_task_kill.cont() {
+                    break;
+                }
+            }
+            LairResult::<()>::Ok(())
+        });
+
+        let (cli_kill, cli_send, _cli_recv) =
+            spawn_ipc_connection(config).await?;
+
+        // `request()` returns a lazy future that does nothing until
+        // polled, so it's driven on its own task here rather than
+        // awaited inline - otherwise awaiting `got_req_recv` below (the
+        // server's confirmation that it received this very request)
+        // would deadlock, since nothing would ever poll the send.
+        let (res_send, res_recv) = futures::channel::oneshot::channel();
+        tokio::task::spawn(async move {
+            let res = cli_send
+                .request(LairWire::ToLairLairGetLastEntryIndex { msg_id: 11 })
+                .await;
+            let _ = res_send.send(res);
+        });
+
+        got_req_recv.await.unwrap();
+        // Dropping this - the only outer handle - flips the connection
+        // to Draining. The request registered above must still complete.
+        drop(cli_kill);
+
+        let res = res_recv.await.unwrap().unwrap();
+        match res {
+            LairWire::ToCliLairGetLastEntryIndexResponse {
+                last_keystore_index,
+                ..
+            } => {
+                assert_eq!(7, last_keystore_index.0);
+            }
+            oth => panic!("unexpected: {:?}", oth),
+        }
+
+        drop(srv_kill);
+        drop(tmpdir);
+
+        Ok(())
+    }
+
+    #[tokio::test(threaded_scheduler)]
+    async fn test_flood_control_kills_connection() -> LairResult<()> {
+        let tmpdir = tempfile::tempdir().unwrap();
+
+        let config = Config::builder()
+            .set_root_path(tmpdir.path())
+            .set_min_msg_interval(std::time::Duration::from_millis(200))
+            .build();
+
+        let (srv_kill, mut srv_recv) = spawn_bind_ipc(config.clone()).await?;
+
+        // Hands the accepted connection's `KillSwitch` out to the test, so
+        // it can assert on the *server's* view of the connection dying
+        // without needing any response to ever come back to the client.
+        let (con_kill_send, con_kill_recv) = futures::channel::oneshot::channel();
+        let mut con_kill_send = Some(con_kill_send);
+
+        let srv_task_kill = srv_kill.clone();
+        err_spawn("test-flood-srv", async move {
+            while let Some((con_kill, con_send, mut con_recv)) =
+                srv_recv.next().await
+            {
+                if let Some(tx) = con_kill_send.take() {
+                    let _ = tx.send(con_kill.clone());
+                }
+                err_spawn("test-flood-con", async move {
+                    let _con_send = con_send;
+                    // Deliberately never respond - flood control is a
+                    // property of the read side alone.
+                    while con_recv.next().await.is_some() {
+                        if !con_kill.cont() {
+                            break;
+                        }
+                    }
+                    LairResult::<()>::Ok(())
+                });
+                if !srv_task_kill.cont() {
+                    break;
+                }
+            }
+            LairResult::<()>::Ok(())
+        });
+
+        let (cli_kill, cli_send, _cli_recv) =
+            spawn_ipc_connection(config).await?;
+
+        // Well past `FLOOD_BURST` + `FLOOD_STRIKES_LIMIT` - a legitimate
+        // client pipelining a handful of requests at startup never gets
+        // anywhere near this many back-to-back, but a flooding peer does.
+        for i in 0..20u64 {
+            let cli_send = cli_send.clone();
+            tokio::task::spawn(async move {
+                let _ = cli_send
+                    .request(LairWire::ToLairLairGetLastEntryIndex {
+                        msg_id: i,
+                    })
+                    .await;
+            });
+        }
+
+        let con_kill = con_kill_recv.await.unwrap();
+        tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            con_kill.wait_dead(),
+        )
+        .await
+        .expect("flood control should have killed the connection");
+        assert!(con_kill.is_dead());
+
+        drop(cli_kill);
+        drop(srv_kill);
+        drop(tmpdir);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,76 @@
+//! Tcp+tls transport, letting a remote holochain conductor reach this lair
+//! keystore across a trust boundary rather than being confined to
+//! same-host unix sockets / named pipes.
+
+use crate::*;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Read half of a tcp+tls connection.
+pub type IpcRead = tokio::io::ReadHalf<tokio_rustls::TlsStream<TcpStream>>;
+
+/// Write half of a tcp+tls connection.
+pub type IpcWrite = tokio::io::WriteHalf<tokio_rustls::TlsStream<TcpStream>>;
+
+/// Bound tcp listener, handing off rustls-wrapped streams to callers.
+pub struct IpcServer {
+    listener: TcpListener,
+    acceptor: tokio_rustls::TlsAcceptor,
+}
+
+impl IpcServer {
+    /// Bind a new tcp listener, ready to accept tls handshakes.
+    ///
+    /// `config.tls_server_config()` must be built with a client cert
+    /// `Verifier` (e.g. `AllowAnyAuthenticatedClient`), not
+    /// `NoClientAuth` - this transport crosses a trust boundary, so the
+    /// tls handshake itself is this crate's only authentication step and
+    /// has to demand a client certificate, not merely encrypt the pipe.
+    pub fn bind(config: Arc<Config>) -> LairResult<Self> {
+        let std_listener = std::net::TcpListener::bind(config.tcp_bind_addr())
+            .map_err(LairError::other)?;
+        std_listener.set_nonblocking(true).map_err(LairError::other)?;
+        let listener = TcpListener::from_std(std_listener)
+            .map_err(LairError::other)?;
+        let acceptor =
+            tokio_rustls::TlsAcceptor::from(config.tls_server_config());
+        Ok(Self { listener, acceptor })
+    }
+
+    /// Accept the next incoming tcp connection and complete its tls
+    /// handshake before handing back the split read/write halves.
+    pub async fn accept(&mut self) -> LairResult<(IpcRead, IpcWrite)> {
+        let (socket, _addr) =
+            self.listener.accept().await.map_err(LairError::other)?;
+        let tls = self
+            .acceptor
+            .accept(socket)
+            .await
+            .map_err(LairError::other)?;
+        Ok(tokio::io::split(tokio_rustls::TlsStream::Server(tls)))
+    }
+}
+
+/// Establish an outgoing tcp+tls client connection to a remote lair server.
+///
+/// `config.tls_client_config()` must supply this side's own client cert +
+/// key, matching the server's `AllowAnyAuthenticatedClient` requirement
+/// above - without it the server rejects the tls handshake.
+pub async fn ipc_connect(
+    config: Arc<Config>,
+) -> LairResult<(IpcRead, IpcWrite)> {
+    let socket = TcpStream::connect(config.tcp_connect_addr())
+        .await
+        .map_err(LairError::other)?;
+    let connector =
+        tokio_rustls::TlsConnector::from(config.tls_client_config());
+    let name =
+        tokio_rustls::webpki::DNSNameRef::try_from_ascii_str(
+            config.tls_server_name(),
+        )
+        .map_err(LairError::other)?;
+    let tls = connector
+        .connect(name, socket)
+        .await
+        .map_err(LairError::other)?;
+    Ok(tokio::io::split(tokio_rustls::TlsStream::Client(tls)))
+}
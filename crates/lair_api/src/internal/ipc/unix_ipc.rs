@@ -0,0 +1,48 @@
+//! Unix domain socket transport, confined to same-host callers.
+
+use crate::*;
+use tokio::net::{UnixListener, UnixStream};
+
+/// Read half of a local unix socket connection.
+pub type IpcRead = tokio::io::ReadHalf<UnixStream>;
+
+/// Write half of a local unix socket connection.
+pub type IpcWrite = tokio::io::WriteHalf<UnixStream>;
+
+fn socket_path(config: &Config) -> std::path::PathBuf {
+    config.root_path().join("socket")
+}
+
+/// Bound unix socket listener.
+pub struct IpcServer {
+    listener: UnixListener,
+}
+
+impl IpcServer {
+    /// Bind a new unix socket listener at `Config::root_path`'s socket
+    /// file, removing any stale socket left behind by a prior process.
+    pub fn bind(config: Arc<Config>) -> LairResult<Self> {
+        let path = socket_path(&config);
+        let _ = std::fs::remove_file(&path);
+        let listener =
+            UnixListener::bind(&path).map_err(LairError::other)?;
+        Ok(Self { listener })
+    }
+
+    /// Accept the next incoming connection.
+    pub async fn accept(&mut self) -> LairResult<(IpcRead, IpcWrite)> {
+        let (socket, _addr) =
+            self.listener.accept().await.map_err(LairError::other)?;
+        Ok(tokio::io::split(socket))
+    }
+}
+
+/// Establish an outgoing client connection to a local unix socket.
+pub async fn ipc_connect(
+    config: Arc<Config>,
+) -> LairResult<(IpcRead, IpcWrite)> {
+    let socket = UnixStream::connect(socket_path(&config))
+        .await
+        .map_err(LairError::other)?;
+    Ok(tokio::io::split(socket))
+}
@@ -0,0 +1,55 @@
+//! Windows named pipe transport, confined to same-host callers - the
+//! windows equivalent of `unix_ipc`.
+
+use crate::*;
+
+// Named pipe names live in a flat namespace under `\\.\pipe\` and can't
+// contain path separators or drive letters, so `root_path` (a full
+// filesystem path) can't be interpolated directly the way `unix_ipc`
+// interpolates it into a socket file path. Hash it instead, so every
+// caller sharing a `root_path` agrees on the same pipe.
+fn pipe_name(config: &Config) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    config.root_path().hash(&mut hasher);
+    format!(r"\\.\pipe\lair-{:x}", hasher.finish())
+}
+
+/// Read half of a local named pipe connection.
+pub type IpcRead = tokio::io::ReadHalf<named_pipe::PipeStream>;
+
+/// Write half of a local named pipe connection.
+pub type IpcWrite = tokio::io::WriteHalf<named_pipe::PipeStream>;
+
+/// Bound named pipe listener.
+pub struct IpcServer {
+    name: String,
+}
+
+impl IpcServer {
+    /// Bind a new named pipe listener at `Config::root_path`'s pipe name.
+    pub fn bind(config: Arc<Config>) -> LairResult<Self> {
+        Ok(Self {
+            name: pipe_name(&config),
+        })
+    }
+
+    /// Accept the next incoming connection.
+    pub async fn accept(&mut self) -> LairResult<(IpcRead, IpcWrite)> {
+        let stream = named_pipe::PipeListener::bind(&self.name)
+            .map_err(LairError::other)?
+            .accept()
+            .map_err(LairError::other)?
+            .into_inner();
+        Ok(tokio::io::split(stream))
+    }
+}
+
+/// Establish an outgoing client connection to a local named pipe.
+pub async fn ipc_connect(
+    config: Arc<Config>,
+) -> LairResult<(IpcRead, IpcWrite)> {
+    let stream = named_pipe::PipeClient::connect(pipe_name(&config))
+        .map_err(LairError::other)?;
+    Ok(tokio::io::split(stream))
+}
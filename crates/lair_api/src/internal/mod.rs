@@ -0,0 +1,5 @@
+//! Internals shared between the client and server halves of a connection.
+
+pub mod wire;
+
+pub mod ipc;
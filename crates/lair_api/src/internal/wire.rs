@@ -0,0 +1,136 @@
+//! The messages sent back and forth over a lair ipc connection, and their
+//! wire framing.
+
+use crate::*;
+use serde::{Deserialize, Serialize};
+
+/// Index of an entry within the keystore.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct KeystoreIndex(pub u64);
+
+impl From<u64> for KeystoreIndex {
+    fn from(u: u64) -> Self {
+        Self(u)
+    }
+}
+
+/// Every message that can be sent over a lair ipc connection, in either
+/// direction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum LairWire {
+    /// Transparent keepalive - answered with a matching `Pong` by the
+    /// peer and otherwise never surfaced to `IpcWireApi` consumers.
+    Ping {
+        /// Message id, echoed back in the `Pong`.
+        msg_id: u64,
+    },
+
+    /// Answer to a `Ping`.
+    Pong {
+        /// Message id, copied from the `Ping` it answers.
+        msg_id: u64,
+    },
+
+    /// Server asking the client to prompt a human for the passphrase
+    /// that unlocks this keystore. Interactive - see
+    /// `Config::interactive_request_timeout`.
+    ToCliRequestUnlockPassphrase {
+        /// Message id.
+        msg_id: u64,
+    },
+
+    /// Response to `ToCliRequestUnlockPassphrase`.
+    ToLairRequestUnlockPassphraseResponse {
+        /// Message id, copied from the request.
+        msg_id: u64,
+        /// The passphrase a human typed in response to the prompt.
+        passphrase: String,
+    },
+
+    /// Client asking the server for the index of the last entry written
+    /// to the keystore.
+    ToLairLairGetLastEntryIndex {
+        /// Message id.
+        msg_id: u64,
+    },
+
+    /// Response to `ToLairLairGetLastEntryIndex`.
+    ToCliLairGetLastEntryIndexResponse {
+        /// Message id, copied from the request.
+        msg_id: u64,
+        /// Index of the last entry written to the keystore.
+        last_keystore_index: KeystoreIndex,
+    },
+}
+
+impl LairWire {
+    /// Given the bytes buffered so far, report how many bytes (including
+    /// the length prefix) the next full message will occupy, once there
+    /// is enough data to know.
+    pub fn peek_size(data: &[u8]) -> LairResult<usize> {
+        if data.len() < 4 {
+            return Err(LairError::other("not enough data to peek size"));
+        }
+        let mut len_bytes = [0_u8; 4];
+        len_bytes.copy_from_slice(&data[..4]);
+        Ok(4 + u32::from_le_bytes(len_bytes) as usize)
+    }
+
+    /// Decode a single framed message. `data` must contain at least
+    /// `peek_size(data)` bytes.
+    pub fn decode(data: &[u8]) -> LairResult<Self> {
+        let size = Self::peek_size(data)?;
+        serde_json::from_slice(&data[4..size]).map_err(LairError::other)
+    }
+
+    /// Frame this message for the wire: a 4-byte little-endian length
+    /// prefix followed by its encoded body.
+    pub fn encode(&self) -> LairResult<Vec<u8>> {
+        let body = serde_json::to_vec(self).map_err(LairError::other)?;
+        let mut out = Vec::with_capacity(4 + body.len());
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    /// The message id this message carries, used to correlate a response
+    /// with the `RespondTrack` entry registered for its original request.
+    pub fn get_msg_id(&self) -> u64 {
+        match self {
+            LairWire::Ping { msg_id }
+            | LairWire::Pong { msg_id }
+            | LairWire::ToCliRequestUnlockPassphrase { msg_id }
+            | LairWire::ToLairRequestUnlockPassphraseResponse {
+                msg_id,
+                ..
+            }
+            | LairWire::ToLairLairGetLastEntryIndex { msg_id }
+            | LairWire::ToCliLairGetLastEntryIndexResponse {
+                msg_id,
+                ..
+            } => *msg_id,
+        }
+    }
+
+    /// Is this a request that expects a matching response delivered back
+    /// up through `IpcWireApi`, as opposed to a response destined for
+    /// `RespondTrack` or a transparently-handled `Ping`/`Pong`?
+    pub fn is_req(&self) -> bool {
+        matches!(
+            self,
+            LairWire::ToCliRequestUnlockPassphrase { .. }
+                | LairWire::ToLairLairGetLastEntryIndex { .. }
+        )
+    }
+
+    /// Is this a request that waits on a human rather than a peer
+    /// process, and therefore needs
+    /// `Config::interactive_request_timeout` instead of
+    /// `Config::default_request_timeout`?
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, LairWire::ToCliRequestUnlockPassphrase { .. })
+    }
+}
@@ -0,0 +1,17 @@
+//! Client/server api for communicating with a lair keystore over a local
+//! ipc socket or an authenticated tcp+tls connection.
+
+pub use std::sync::Arc;
+
+mod error;
+pub use error::{LairError, LairResult};
+
+mod config;
+pub use config::Config;
+
+pub mod internal;
+pub use internal::ipc::{
+    spawn_bind_ipc, spawn_ipc_connection, IncomingIpcReceiver, IpcReceiver,
+    IpcSender, Transport,
+};
+pub use internal::wire::{KeystoreIndex, LairWire};